@@ -0,0 +1,71 @@
+//! The `paintr_core::CanvasData` methods this crate's layer management and
+//! native `.paintr` format build on: `open_document`/`save_document` for the
+//! full layer stack, and the `set_active_layer`/`set_layer_visible`/
+//! `remove_layer`/`reorder_layers`/`layer_rows` passthroughs the layers panel
+//! dispatches onto. Lives alongside `plane.rs` as the paintr_core-side half
+//! of this series; `main.rs` calls these through the `CanvasData` it gets
+//! from `paintr_core`.
+
+use crate::layers_panel::LayerRow;
+use crate::plane::{PlaneIndex, Planes};
+use image::DynamicImage;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Stand-in for the parts of `paintr_core::CanvasData` this crate relies on:
+/// a path plus the layer stack it's built on top of (see `plane.rs`'s
+/// `Planes`). The rest of `CanvasData` (flattened `new`/`save`/`open`,
+/// `merged`, `selection`, undo integration, ...) predates this series and
+/// lives in `paintr_core` proper.
+struct CanvasData {
+    path: PathBuf,
+    planes: Planes,
+}
+
+impl CanvasData {
+    #[allow(dead_code)]
+    fn new(path: impl Into<PathBuf>, image: DynamicImage) -> CanvasData {
+        let mut planes = Planes::new();
+        planes.push(Arc::new(image));
+        CanvasData { path: path.into(), planes }
+    }
+
+    /// Opens a native `.paintr` document, restoring its full layer stack
+    /// rather than the single flattened image `open` gives you.
+    pub(crate) fn open_document(path: &Path) -> Result<CanvasData, Error> {
+        let bytes = std::fs::read(path)?;
+        let planes = Planes::from_document_bytes(&bytes)?;
+        Ok(CanvasData { path: path.to_path_buf(), planes })
+    }
+
+    /// Writes the full layer stack (not just the flattened image `save`
+    /// exports) to `path` in the native `.paintr` format.
+    pub(crate) fn save_document(&mut self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.planes.to_document_bytes()?)?;
+        self.path = path.to_path_buf();
+        Ok(())
+    }
+
+    pub(crate) fn set_active_layer(&mut self, index: usize) {
+        self.planes.set_active(PlaneIndex::new(index));
+    }
+
+    pub(crate) fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        self.planes.set_visible(PlaneIndex::new(index), visible);
+    }
+
+    pub(crate) fn remove_layer(&mut self, index: usize) {
+        self.planes.remove(PlaneIndex::new(index));
+    }
+
+    pub(crate) fn reorder_layers(&mut self, from: usize, to: usize) {
+        self.planes.reorder(PlaneIndex::new(from), PlaneIndex::new(to));
+    }
+
+    pub(crate) fn layer_rows(&self) -> Vec<LayerRow> {
+        self.planes.layer_rows()
+    }
+}
@@ -0,0 +1,24 @@
+//! `Selector`s for the commands the main menu and delegate exchange.
+
+use druid::Selector;
+
+use std::path::PathBuf;
+
+use crate::dialogs::NewFileSettings;
+
+pub(crate) const FILE_EXIT_ACTION: Selector = Selector::new("paintr.file-exit");
+pub(crate) const FILE_NEW_ACTION: Selector = Selector::new("paintr.file-new");
+pub(crate) const FILE_NEW_CLIPBOARD_ACTION: Selector = Selector::new("paintr.file-new-clipboard");
+pub(crate) const NEW_IMAGE_ACTION: Selector<NewFileSettings> =
+    Selector::new("paintr.new-image-settings-confirmed");
+
+pub(crate) const EDIT_UNDO_ACTION: Selector = Selector::new("paintr.edit-undo");
+pub(crate) const EDIT_REDO_ACTION: Selector = Selector::new("paintr.edit-redo");
+pub(crate) const EDIT_COPY_ACTION: Selector = Selector::new("paintr.edit-copy");
+pub(crate) const EDIT_PASTE_ACTION: Selector = Selector::new("paintr.edit-paste");
+
+pub(crate) const ABOUT_TEST_ACTION: Selector = Selector::new("paintr.about-test");
+
+/// Reopens a path previously listed in the "Recent Files" menu.
+pub(crate) const OPEN_RECENT_FILE_ACTION: Selector<PathBuf> =
+    Selector::new("paintr.open-recent-file");
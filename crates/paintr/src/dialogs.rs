@@ -0,0 +1,195 @@
+//! Modal dialogs shown over the main window, tracked via `AppState::modal`
+//! and rendered by `modal_builder` alongside the canvas in `ui::ui_builder`.
+
+use druid::widget::{Button, Flex, Label, SizedBox, Stepper, ViewSwitcher};
+use druid::{lens, Data, Lens, LensExt, LocalizedString, Widget, WidgetExt};
+
+use crate::preferences::{Config, SerializableColor};
+use crate::{commands, AppState};
+
+#[derive(Clone, Data, Lens, Debug, Default)]
+pub(crate) struct NewFileSettings {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Clone, Data, Debug)]
+pub(crate) enum DialogData {
+    NewFileSettings(NewFileSettings),
+    Preferences(Config),
+}
+
+impl DialogData {
+    /// Opens the "New File" dialog, pre-filled with the configured default
+    /// canvas size.
+    pub(crate) fn new_file_settings(config: &Config) -> DialogData {
+        DialogData::NewFileSettings(NewFileSettings {
+            width: Some(config.default_canvas_width),
+            height: Some(config.default_canvas_height),
+        })
+    }
+
+    pub(crate) fn preferences_settings(config: Config) -> DialogData {
+        DialogData::Preferences(config)
+    }
+}
+
+/// Renders whichever dialog `AppState::modal` currently holds, or nothing.
+pub(crate) fn modal_builder() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _| data.modal.clone(),
+        |modal, _data, _env| match modal {
+            None => Box::new(SizedBox::empty()) as Box<dyn Widget<AppState>>,
+            Some(DialogData::NewFileSettings(settings)) => Box::new(new_file_dialog(settings.clone())),
+            Some(DialogData::Preferences(_)) => Box::new(preferences_dialog()),
+        },
+    )
+}
+
+fn new_file_dialog(settings: NewFileSettings) -> impl Widget<AppState> {
+    Flex::column()
+        .with_child(Label::new(LocalizedString::new("dialog-new-file-title")))
+        .with_child(Label::new(format!(
+            "{}\u{00d7}{}",
+            settings.width.unwrap_or(0),
+            settings.height.unwrap_or(0)
+        )))
+        .with_child(Button::new(LocalizedString::new("dialog-new-file-create")).on_click(
+            move |ctx, data: &mut AppState, _| {
+                data.modal = None;
+                ctx.submit_command(commands::NEW_IMAGE_ACTION.with(settings.clone()));
+            },
+        ))
+        .with_child(cancel_button())
+}
+
+/// Projects `AppState::modal` onto the `Config` of an open preferences
+/// dialog. A no-op default when some other dialog (or none) is open, since
+/// the preferences widgets are only ever mounted while this variant is
+/// active.
+#[derive(Clone, Copy)]
+struct PreferencesConfigLens;
+
+impl Lens<AppState, Config> for PreferencesConfigLens {
+    fn with<V, F: FnOnce(&Config) -> V>(&self, data: &AppState, f: F) -> V {
+        match &data.modal {
+            Some(DialogData::Preferences(config)) => f(config),
+            _ => f(&Config::default()),
+        }
+    }
+
+    fn with_mut<V, F: FnOnce(&mut Config) -> V>(&self, data: &mut AppState, f: F) -> V {
+        match &mut data.modal {
+            Some(DialogData::Preferences(config)) => f(config),
+            _ => f(&mut Config::default()),
+        }
+    }
+}
+
+/// Adapts an integer-valued lens to the `f64` that `Stepper` operates on.
+struct AsF64<L>(L);
+
+impl<T, N: Numeric, L: Lens<T, N>> Lens<T, f64> for AsF64<L> {
+    fn with<V, F: FnOnce(&f64) -> V>(&self, data: &T, f: F) -> V {
+        self.0.with(data, |v| f(&v.to_f64()))
+    }
+
+    fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut T, f: F) -> V {
+        self.0.with_mut(data, |v| {
+            let mut as_f64 = v.to_f64();
+            let result = f(&mut as_f64);
+            *v = N::from_f64(as_f64);
+            result
+        })
+    }
+}
+
+trait Numeric: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(v: f64) -> Self;
+}
+
+impl Numeric for u8 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(v: f64) -> Self {
+        v.max(0.0).min(255.0) as u8
+    }
+}
+
+impl Numeric for u32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(v: f64) -> Self {
+        v.max(1.0) as u32
+    }
+}
+
+impl Numeric for u64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(v: f64) -> Self {
+        v.max(1.0) as u64
+    }
+}
+
+fn preferences_dialog() -> impl Widget<AppState> {
+    Flex::column()
+        .with_child(Label::new(LocalizedString::new("dialog-preferences-title")))
+        .with_child(numeric_field("dialog-preferences-window-width", PreferencesConfigLens.then(lens!(Config, window_width))))
+        .with_child(numeric_field(
+            "dialog-preferences-window-height",
+            PreferencesConfigLens.then(lens!(Config, window_height)),
+        ))
+        .with_child(color_field("dialog-preferences-background", PreferencesConfigLens.then(lens!(Config, window_background))))
+        .with_child(numeric_field(
+            "dialog-preferences-canvas-width",
+            AsF64(PreferencesConfigLens.then(lens!(Config, default_canvas_width))),
+        ))
+        .with_child(numeric_field(
+            "dialog-preferences-canvas-height",
+            AsF64(PreferencesConfigLens.then(lens!(Config, default_canvas_height))),
+        ))
+        .with_child(color_field("dialog-preferences-fill", PreferencesConfigLens.then(lens!(Config, new_file_fill))))
+        .with_child(numeric_field(
+            "dialog-preferences-autosave-interval",
+            AsF64(PreferencesConfigLens.then(lens!(Config, autosave_interval_secs))),
+        ))
+        .with_child(Button::new(LocalizedString::new("dialog-preferences-save")).on_click(
+            |ctx, data: &mut AppState, _| {
+                if let Some(DialogData::Preferences(config)) = data.modal.take() {
+                    ctx.submit_command(crate::preferences::PREFERENCES_APPLY.with(config));
+                }
+            },
+        ))
+        .with_child(cancel_button())
+}
+
+/// A labeled row editing a single numeric field via a `Stepper`.
+fn numeric_field(label: &'static str, lens: impl Lens<AppState, f64> + 'static) -> impl Widget<AppState> {
+    Flex::row()
+        .with_child(Label::new(LocalizedString::new(label)))
+        .with_flex_spacer(1.0)
+        .with_child(Label::dynamic(|v: &f64, _| format!("{:.0}", v)))
+        .with_child(Stepper::new())
+        .lens(lens)
+}
+
+/// A labeled row editing an RGB `SerializableColor` via one `Stepper` per
+/// channel.
+fn color_field(label: &'static str, lens: impl Lens<AppState, SerializableColor> + Clone + 'static) -> impl Widget<AppState> {
+    Flex::row()
+        .with_child(Label::new(LocalizedString::new(label)))
+        .with_flex_spacer(1.0)
+        .with_child(Stepper::new().lens(AsF64(lens.clone().then(lens!(SerializableColor, 0)))))
+        .with_child(Stepper::new().lens(AsF64(lens.clone().then(lens!(SerializableColor, 1)))))
+        .with_child(Stepper::new().lens(AsF64(lens.then(lens!(SerializableColor, 2)))))
+}
+
+fn cancel_button() -> impl Widget<AppState> {
+    Button::new(LocalizedString::new("dialog-cancel"))
+        .on_click(|_, data: &mut AppState, _| data.modal = None)
+}
@@ -0,0 +1,129 @@
+//! Dockable layers panel: lists the planes of the active document with a
+//! thumbnail, a visibility toggle, and reorder/delete controls. Wired into
+//! the app shell alongside the canvas built by `ui::ui_builder`.
+
+use druid::im::Vector;
+use druid::widget::{Flex, Image, Label, List, ViewSwitcher};
+use druid::{lens, Data, ImageBuf, Lens, Selector, Widget, WidgetExt};
+use image::{DynamicImage, GenericImageView};
+
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// Sets the active layer (by index into the current document's planes).
+pub(crate) const LAYER_SET_ACTIVE: Selector<usize> = Selector::new("paintr.layer-set-active");
+/// Sets a layer's visibility: `(index, visible)`.
+pub(crate) const LAYER_SET_VISIBLE: Selector<(usize, bool)> =
+    Selector::new("paintr.layer-set-visible");
+/// Removes a layer by index.
+pub(crate) const LAYER_REMOVE: Selector<usize> = Selector::new("paintr.layer-remove");
+/// Reorders a layer: `(from, to)`.
+pub(crate) const LAYER_REORDER: Selector<(usize, usize)> = Selector::new("paintr.layer-reorder");
+
+const THUMBNAIL_SIZE: f64 = 32.0;
+
+/// One layer row as projected for display in the layers panel.
+#[derive(Clone, Lens, Debug)]
+pub(crate) struct LayerRow {
+    pub index: usize,
+    pub name: String,
+    pub visible: bool,
+    pub active: bool,
+    /// The layer's own pixels at full size; the panel scales them down to a
+    /// thumbnail only when drawing. Compared by
+    /// identity rather than contents, same as `Planes`' own `Data` impl.
+    pub thumbnail: Arc<DynamicImage>,
+}
+
+impl PartialEq for LayerRow {
+    fn eq(&self, other: &LayerRow) -> bool {
+        self.index == other.index
+            && self.name == other.name
+            && self.visible == other.visible
+            && self.active == other.active
+            && Arc::ptr_eq(&self.thumbnail, &other.thumbnail)
+    }
+}
+
+impl Data for LayerRow {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+pub(crate) fn layers_panel_builder() -> impl Widget<AppState> {
+    List::new(layer_row_builder).lens(lens::Map::new(layer_rows, |_, _: Vector<LayerRow>| {})).expand_height()
+}
+
+/// Projects the active document's planes into display rows. Rows are
+/// read-only from the panel's point of view; edits are dispatched as
+/// `LAYER_*` commands and round-trip back through `AppState` normally.
+fn layer_rows(data: &AppState) -> Vector<LayerRow> {
+    match &data.editor.canvas {
+        Some(canvas) => canvas.layer_rows().into_iter().collect(),
+        None => Vector::new(),
+    }
+}
+
+fn layer_row_builder() -> impl Widget<LayerRow> {
+    Flex::row()
+        .with_child(thumbnail())
+        .with_child(visibility_toggle())
+        .with_flex_child(
+            Label::dynamic(|row: &LayerRow, _| row.name.clone()).expand_width().on_click(
+                |ctx, row: &mut LayerRow, _| {
+                    ctx.submit_command(LAYER_SET_ACTIVE.with(row.index));
+                },
+            ),
+            1.0,
+        )
+        .with_child(reorder_button("\u{2191}", |index| index.checked_add(1)))
+        .with_child(reorder_button("\u{2193}", |index| index.checked_sub(1)))
+        .with_child(remove_button())
+}
+
+/// Rebuilds the `Image` widget whenever the backing pixels change identity,
+/// since `ImageBuf`s themselves aren't cheap to compare or rebuild per frame.
+fn thumbnail() -> impl Widget<LayerRow> {
+    ViewSwitcher::new(
+        |row: &LayerRow, _| Arc::as_ptr(&row.thumbnail) as usize,
+        |_, row: &LayerRow, _| Box::new(thumbnail_image(&row.thumbnail)) as Box<dyn Widget<LayerRow>>,
+    )
+    .fix_size(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+}
+
+fn thumbnail_image(image: &DynamicImage) -> impl Widget<LayerRow> {
+    let (width, height) = image.dimensions();
+    let buf = ImageBuf::from_raw(
+        image.to_rgba8().into_raw(),
+        druid::piet::ImageFormat::RgbaSeparate,
+        width as usize,
+        height as usize,
+    );
+    Image::new(buf)
+}
+
+fn visibility_toggle() -> impl Widget<LayerRow> {
+    Label::dynamic(|row: &LayerRow, _| if row.visible { "\u{1F441}" } else { " " }.to_owned())
+        .on_click(|ctx, row: &mut LayerRow, _| {
+            ctx.submit_command(LAYER_SET_VISIBLE.with((row.index, !row.visible)));
+        })
+}
+
+/// An up/down button that submits `LAYER_REORDER` from this row's index to
+/// whatever `step` resolves to, unless `step` over- or underflows. An index
+/// past the last layer is still submitted; `Planes::reorder` ignores it.
+fn reorder_button(glyph: &'static str, step: fn(usize) -> Option<usize>) -> impl Widget<LayerRow> {
+    Label::new(glyph).on_click(move |ctx, row: &mut LayerRow, _| {
+        if let Some(to) = step(row.index) {
+            ctx.submit_command(LAYER_REORDER.with((row.index, to)));
+        }
+    })
+}
+
+fn remove_button() -> impl Widget<LayerRow> {
+    Label::new("\u{2715}").on_click(|ctx, row: &mut LayerRow, _| {
+        ctx.submit_command(LAYER_REMOVE.with(row.index));
+    })
+}
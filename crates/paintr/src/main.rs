@@ -6,7 +6,10 @@ macro_rules! L {
 
 mod commands;
 mod dialogs;
+mod layers_panel;
 mod menu;
+mod preferences;
+mod project_store;
 mod tools;
 mod ui;
 
@@ -26,12 +29,14 @@ use std::{
 };
 
 use dialogs::DialogData;
+use preferences::Config;
+use project_store::ProjectStore;
 use tools::ToolKind;
 use ui::ui_builder;
 use widgets::notif_bar::Notification;
 
 fn main() {
-    let app_state = AppState {
+    let mut app_state = AppState {
         notifications: Arc::new(Vec::new()),
         modal: None,
         editor: EditorState {
@@ -43,19 +48,30 @@ fn main() {
         },
     };
 
+    let config = Config::load();
+    let interval = std::time::Duration::from_secs(config.autosave_interval_secs.max(1));
+    let delegate = Delegate::new(config.clone());
+
+    match &delegate.store {
+        None => app_state.show_notification(Notification::error(
+            "Could not open the crash-recovery store; running without autosave.",
+        )),
+        Some(store) => app_state.restore_last_session(store),
+    }
+
     let main_window = WindowDesc::new(ui_builder)
         .title(L!("paint-app-name"))
-        .menu(menu::make_menu(&app_state))
-        .window_size((800.0, 600.0));
+        .menu(menu::make_menu(&app_state, &delegate.config.recent_files))
+        .window_size((config.window_width, config.window_height));
 
     let user_l10n = find_user_l10n();
 
-    let launcher = AppLauncher::with_window(main_window)
-        .delegate(Delegate::default())
-        .configure_env(|mut env, _| {
-            env.set(theme::WINDOW_BACKGROUND_COLOR, Color::rgb8(0, 0x77, 0x88));
+    let launcher = AppLauncher::with_window(main_window).delegate(delegate).configure_env(
+        move |mut env, _| {
+            env.set(theme::WINDOW_BACKGROUND_COLOR, Color::from(config.window_background));
             theme_ext::init(&mut env);
-        });
+        },
+    );
 
     let launcher = match user_l10n {
         Some(basedir) => launcher.localization_resources(
@@ -65,12 +81,35 @@ fn main() {
         None => launcher,
     };
 
+    spawn_autosave_ticker(launcher.get_external_handle(), interval);
+
     launcher.launch(app_state).expect("launch failed");
 }
 
-#[derive(Default, Debug)]
 struct Delegate {
     windows: Vec<WindowId>,
+    /// `None` when the project store failed to open (disk full, stale lock,
+    /// permissions, ...). The app still runs, just without crash recovery.
+    store: Option<ProjectStore>,
+    config: Config,
+    /// Set by edits since the last `AUTOSAVE_TICK`; cleared by the next tick,
+    /// which attempts the snapshot. A snapshot that could not be written is
+    /// not retried until a later edit sets the flag again. Keeps the PNG re-encode + LMDB write
+    /// off the hot path of every single undo/redo/paste.
+    dirty: bool,
+}
+
+impl Delegate {
+    fn new(config: Config) -> Delegate {
+        let store = ProjectStore::open().ok();
+        Delegate { windows: Vec::new(), store, config, dirty: false }
+    }
+
+    /// Marks the document as needing an autosave on the next `AUTOSAVE_TICK`,
+    /// rather than snapshotting synchronously right away.
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
 }
 
 type Error = Box<dyn std::error::Error>;
@@ -91,12 +130,57 @@ fn to_rgba(img: image::DynamicImage) -> image::DynamicImage {
     })
 }
 
+/// The file extension used for native, layer-preserving documents. Any other
+/// extension goes through the flattened `image` crate export path instead.
+const DOCUMENT_EXTENSION: &str = "paintr";
+
+/// Whether `path` is a native `.paintr` document, as opposed to a flattened
+/// image export (PNG/JPEG/etc.).
+fn is_document_path(path: &std::path::Path) -> bool {
+    path.extension().and_then(std::ffi::OsStr::to_str).map_or(false, |ext| ext == DOCUMENT_EXTENSION)
+}
+
 impl AppState {
     fn show_notification(&mut self, n: Notification) {
         Arc::make_mut(&mut self.notifications).push(n);
     }
 
+    /// Reopens an autosaved document left over from a previous session, if
+    /// any: the first one in the store's document-id order, not necessarily
+    /// the most recent (snapshots carry no timestamp), and only that one. Loads its flattened snapshot straight back
+    /// into the canvas rather than just hinting that one exists.
+    fn restore_last_session(&mut self, store: &ProjectStore) {
+        let doc = match store.unsaved_documents().ok().and_then(|docs| docs.into_iter().next()) {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let restored = store.restore(&doc.doc_id).ok().flatten().and_then(|png| {
+            let img = image::load_from_memory(&png).ok()?;
+            let path = doc.path.clone().unwrap_or_else(|| PathBuf::from(NEW_FILE_NAME));
+            Some(CanvasData::new(path, to_rgba(img)))
+        });
+
+        match restored {
+            Some(canvas) => {
+                self.editor.canvas = Some(canvas);
+                self.show_notification(Notification::info(format!(
+                    "Restored your last session ({})",
+                    doc.path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| NEW_FILE_NAME.to_owned())
+                )));
+            }
+            None => self.show_notification(Notification::error(
+                "Found a recovery snapshot from your last session, but it could not be restored.",
+            )),
+        }
+    }
+
     fn do_open_image(&mut self, path: &std::path::Path) -> Result<(), Error> {
+        if is_document_path(path) {
+            self.editor.canvas = Some(CanvasData::open_document(path)?);
+            return Ok(());
+        }
+
         let img = image::open(path)?;
         self.editor.canvas = Some(CanvasData::new(path, to_rgba(img)));
         Ok(())
@@ -109,15 +193,16 @@ impl AppState {
         Ok(())
     }
 
-    fn do_new_image(&mut self, info: &dialogs::NewFileSettings) -> Result<(), Error> {
+    fn do_new_image(
+        &mut self,
+        info: &dialogs::NewFileSettings,
+        fill: preferences::SerializableColor,
+    ) -> Result<(), Error> {
         let (w, h) = (
             info.width.expect("It must be valid after dialog closed."),
             info.height.expect("It must be valid after dialog closed."),
         );
-        // Fill with white color
-        let img = image::ImageBuffer::from_fn(w, h, |_, _| {
-            image::Rgba([0xff_u8, 0xff_u8, 0xff_u8, 0xff_u8])
-        });
+        let img = image::ImageBuffer::from_fn(w, h, |_, _| image::Rgba([fill.0, fill.1, fill.2, 0xff]));
 
         self.editor.canvas =
             Some(CanvasData::new(NEW_FILE_NAME, image::DynamicImage::ImageRgba8(img)));
@@ -126,7 +211,13 @@ impl AppState {
 
     fn do_save_as_image(&mut self, path: &std::path::Path) -> Result<(), Error> {
         let canvas = self.editor.canvas.as_mut().ok_or_else(|| "No image was found.")?;
-        canvas.save(path)?;
+
+        if is_document_path(path) {
+            canvas.save_document(path)?;
+        } else {
+            canvas.save(path)?;
+        }
+
         Ok(())
     }
 
@@ -164,6 +255,12 @@ impl AppState {
     fn status(&self) -> Option<String> {
         Some(self.editor.canvas.as_ref()?.selection()?.description())
     }
+
+    /// A stable id for the current document used to key its autosave
+    /// snapshot, derived from its path (or `NEW_FILE_NAME` while untitled).
+    fn document_id(&self) -> String {
+        self.image_file_name()
+    }
 }
 
 impl Delegate {
@@ -178,13 +275,14 @@ impl Delegate {
                 ctx.submit_command(druid::commands::CLOSE_WINDOW);
             }
             _ if cmd.is(commands::FILE_NEW_ACTION) => {
-                data.modal = Some(DialogData::new_file_settings());
+                data.modal = Some(DialogData::new_file_settings(&self.config));
                 self.update_menu(data, ctx);
             }
             _ if cmd.is(commands::FILE_NEW_CLIPBOARD_ACTION) => {
                 data.do_new_image_from_clipboard()?;
                 data.show_notification(Notification::info("New file created"));
                 self.update_menu(data, ctx);
+                self.mark_dirty();
             }
             _ if cmd.is(druid::commands::OPEN_FILE) => {
                 let info = cmd.get_unchecked(druid::commands::OPEN_FILE);
@@ -193,25 +291,50 @@ impl Delegate {
                     "{} opened",
                     data.image_file_name()
                 )));
+                self.remember_recent_file(info.path());
                 self.update_menu(data, ctx);
+                self.mark_dirty();
             }
             _ if cmd.is(druid::commands::SAVE_FILE_AS) => {
                 let info = cmd.get_unchecked(druid::commands::SAVE_FILE_AS);
+                // Captured before the save, which updates the canvas's path
+                // and therefore `document_id()` — forgetting under the new
+                // id would miss the autosave entry actually keyed by this one.
+                let previous_doc_id = data.document_id();
                 data.do_save_as_image(info.path())?;
                 data.show_notification(Notification::info(format!(
                     "{} saved",
                     data.image_file_name()
                 )));
+                self.remember_recent_file(info.path());
                 self.update_menu(data, ctx);
+                // The document now lives at a user-chosen path; the crash
+                // recovery copy of it is no longer needed.
+                if let Some(store) = &self.store {
+                    let _ = store.forget(&previous_doc_id);
+                }
+            }
+            _ if cmd.is(commands::OPEN_RECENT_FILE_ACTION) => {
+                let path = cmd.get_unchecked(commands::OPEN_RECENT_FILE_ACTION).clone();
+                data.do_open_image(&path)?;
+                data.show_notification(Notification::info(format!(
+                    "{} opened",
+                    data.image_file_name()
+                )));
+                self.remember_recent_file(&path);
+                self.update_menu(data, ctx);
+                self.mark_dirty();
             }
             _ if cmd.is(commands::EDIT_UNDO_ACTION) => {
                 if let Some(desc) = data.editor.do_undo() {
                     data.show_notification(Notification::info(format!("Undo {}", desc)));
+                    self.mark_dirty();
                 }
             }
             _ if cmd.is(commands::EDIT_REDO_ACTION) => {
                 if let Some(desc) = data.editor.do_redo() {
                     data.show_notification(Notification::info(format!("Redo {}", desc)));
+                    self.mark_dirty();
                 }
             }
             _ if cmd.is(commands::EDIT_COPY_ACTION) => {
@@ -222,30 +345,108 @@ impl Delegate {
             _ if cmd.is(commands::EDIT_PASTE_ACTION) => {
                 if data.do_paste()? {
                     data.show_notification(Notification::info("Pasted"));
+                    self.mark_dirty();
                 }
             }
             _ if cmd.is(commands::NEW_IMAGE_ACTION) => {
                 let info = cmd.get_unchecked(commands::NEW_IMAGE_ACTION);
-                data.do_new_image(info)?;
+                data.do_new_image(info, self.config.new_file_fill)?;
                 data.show_notification(Notification::info("New file created"));
                 self.update_menu(data, ctx);
+                self.mark_dirty();
             }
             _ if cmd.is(commands::ABOUT_TEST_ACTION) => {
                 data.show_notification(Notification::info("Test"));
             }
+            _ if cmd.is(project_store::AUTOSAVE_TICK) => {
+                if self.dirty {
+                    self.autosave(data);
+                    self.dirty = false;
+                }
+            }
+            _ if cmd.is(preferences::PREFERENCES_OPEN_ACTION) => {
+                data.modal = Some(DialogData::preferences_settings(self.config.clone()));
+            }
+            _ if cmd.is(preferences::PREFERENCES_APPLY) => {
+                let config = cmd.get_unchecked(preferences::PREFERENCES_APPLY).clone();
+                config.save()?;
+                self.config = config;
+                data.show_notification(Notification::info("Preferences saved"));
+            }
+            _ if cmd.is(layers_panel::LAYER_SET_ACTIVE) => {
+                let index = *cmd.get_unchecked(layers_panel::LAYER_SET_ACTIVE);
+                if let Some(canvas) = data.editor.canvas.as_mut() {
+                    canvas.set_active_layer(index);
+                }
+            }
+            _ if cmd.is(layers_panel::LAYER_SET_VISIBLE) => {
+                let (index, visible) = *cmd.get_unchecked(layers_panel::LAYER_SET_VISIBLE);
+                if let Some(canvas) = data.editor.canvas.as_mut() {
+                    canvas.set_layer_visible(index, visible);
+                }
+            }
+            _ if cmd.is(layers_panel::LAYER_REMOVE) => {
+                let index = *cmd.get_unchecked(layers_panel::LAYER_REMOVE);
+                if let Some(canvas) = data.editor.canvas.as_mut() {
+                    canvas.remove_layer(index);
+                    self.mark_dirty();
+                }
+            }
+            _ if cmd.is(layers_panel::LAYER_REORDER) => {
+                let (from, to) = *cmd.get_unchecked(layers_panel::LAYER_REORDER);
+                if let Some(canvas) = data.editor.canvas.as_mut() {
+                    canvas.reorder_layers(from, to);
+                    self.mark_dirty();
+                }
+            }
             _ => return Ok(Handled::No),
         }
 
         Ok(Handled::Yes)
     }
 
+    /// Snapshots the current document into the `ProjectStore` for crash
+    /// recovery. Only runs from `AUTOSAVE_TICK` when `dirty` is set, so the
+    /// PNG re-encode + LMDB write happen at most once per
+    /// `autosave_interval_secs` instead of inline on every edit command.
+    fn autosave(&mut self, data: &AppState) {
+        let canvas = match data.editor.canvas.as_ref() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let merged = match canvas.merged() {
+            Some(img) => img,
+            None => return,
+        };
+
+        let mut png = Vec::new();
+        if merged.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).is_err() {
+            return;
+        }
+
+        let doc_id = data.document_id();
+        let path = canvas.path();
+        let path = if path == std::path::Path::new(NEW_FILE_NAME) { None } else { Some(path) };
+
+        if let Some(store) = &self.store {
+            let _ = store.snapshot(&doc_id, path, png);
+        }
+    }
+
     fn update_menu(&self, data: &AppState, ctx: &mut DelegateCtx) {
-        let menu = menu::make_menu(data);
+        let menu = menu::make_menu(data, &self.config.recent_files);
 
         for id in &self.windows {
             ctx.set_menu(menu.clone(), *id);
         }
     }
+
+    /// Records `path` in the persisted recent-files list. The "Recent Files"
+    /// menu is not rebuilt here; callers follow up with `update_menu`.
+    fn remember_recent_file(&mut self, path: &std::path::Path) {
+        self.config.push_recent_file(path);
+        let _ = self.config.save();
+    }
 }
 
 impl AppDelegate<AppState> for Delegate {
@@ -295,6 +496,19 @@ impl AppDelegate<AppState> for Delegate {
     }
 }
 
+/// Drives `AUTOSAVE_TICK` from a background thread so the delegate's
+/// autosave check runs on a timer instead of being triggered by, and
+/// blocking, every single edit command.
+fn spawn_autosave_ticker(sink: druid::ExtEventSink, interval: std::time::Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        if sink.submit_command(project_store::AUTOSAVE_TICK, (), Target::Auto).is_err() {
+            // The app has shut down and the event sink is gone.
+            return;
+        }
+    });
+}
+
 fn find_user_l10n() -> Option<PathBuf> {
     let paths = vec![
         path::PathBuf::from("./resources/i18n/"),
@@ -0,0 +1,69 @@
+//! Builds the application menu bar, rebuilt by `Delegate::update_menu`
+//! whenever the document or the recent-files list changes.
+
+use druid::{Command, LocalizedString, MenuDesc, MenuItem, Target};
+
+use std::path::{Path, PathBuf};
+
+use crate::{commands, preferences, AppState};
+
+pub(crate) fn make_menu(_data: &AppState, recent_files: &[PathBuf]) -> MenuDesc<AppState> {
+    MenuDesc::empty()
+        .append(file_menu(recent_files))
+        .append(edit_menu())
+        .append(help_menu())
+}
+
+fn file_menu(recent_files: &[PathBuf]) -> MenuDesc<AppState> {
+    MenuDesc::new(LocalizedString::new("menu-file-menu"))
+        .append(MenuItem::new(LocalizedString::new("menu-file-new"), commands::FILE_NEW_ACTION))
+        .append(MenuItem::new(
+            LocalizedString::new("menu-file-new-from-clipboard"),
+            commands::FILE_NEW_CLIPBOARD_ACTION,
+        ))
+        .append(MenuItem::new(LocalizedString::new("menu-file-open"), druid::commands::SHOW_OPEN_PANEL))
+        .append(recent_files_menu(recent_files))
+        .append(MenuItem::new(LocalizedString::new("menu-file-save-as"), druid::commands::SHOW_SAVE_PANEL))
+        .append(MenuItem::new(
+            LocalizedString::new("menu-file-preferences"),
+            preferences::PREFERENCES_OPEN_ACTION,
+        ))
+        .append(MenuItem::new(LocalizedString::new("menu-file-exit"), commands::FILE_EXIT_ACTION))
+}
+
+/// The "Recent Files" submenu: one entry per remembered path, each
+/// reopening it via `OPEN_RECENT_FILE_ACTION`. Empty (but still present)
+/// when nothing has been opened yet.
+fn recent_files_menu(recent_files: &[PathBuf]) -> MenuDesc<AppState> {
+    let mut menu = MenuDesc::new(LocalizedString::new("menu-file-recent"));
+
+    if recent_files.is_empty() {
+        return menu;
+    }
+
+    for path in recent_files {
+        let label = LocalizedString::new("menu-file-recent-item")
+            .with_placeholder(file_name(path));
+        let command = Command::new(commands::OPEN_RECENT_FILE_ACTION, path.clone(), Target::Auto);
+        menu = menu.append(MenuItem::new(label, command));
+    }
+
+    menu
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|it| it.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string_lossy().into_owned())
+}
+
+fn edit_menu() -> MenuDesc<AppState> {
+    MenuDesc::new(LocalizedString::new("menu-edit-menu"))
+        .append(MenuItem::new(LocalizedString::new("menu-edit-undo"), commands::EDIT_UNDO_ACTION))
+        .append(MenuItem::new(LocalizedString::new("menu-edit-redo"), commands::EDIT_REDO_ACTION))
+        .append(MenuItem::new(LocalizedString::new("menu-edit-copy"), commands::EDIT_COPY_ACTION))
+        .append(MenuItem::new(LocalizedString::new("menu-edit-paste"), commands::EDIT_PASTE_ACTION))
+}
+
+fn help_menu() -> MenuDesc<AppState> {
+    MenuDesc::new(LocalizedString::new("menu-help-menu"))
+        .append(MenuItem::new(LocalizedString::new("menu-about-test"), commands::ABOUT_TEST_ACTION))
+}
@@ -1,9 +1,11 @@
-use crate::image_utils;
+use crate::layers_panel::LayerRow;
 use crate::Paintable;
 use druid::kurbo::Affine;
 use druid::{Data, Point, RenderContext, Size, Vec2};
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use serde::{Deserialize, Serialize};
 
+use std::io::Cursor;
 use std::sync::Arc;
 
 pub enum Plane {
@@ -48,28 +50,122 @@ impl Plane {
     }
 }
 
+// `Plane` round-trips through PNG bytes so that the native `.paintr`
+// document format stays a plain, portable container instead of depending on
+// an in-memory representation of `image::DynamicImage`.
+impl Serialize for Plane {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let Plane::Image(img) = self;
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(serde::ser::Error::custom)?;
+        png.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Plane {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let png = Vec::<u8>::deserialize(deserializer)?;
+        let img = image::load_from_memory(&png).map_err(serde::de::Error::custom)?;
+        Ok(Plane::Image(Arc::new(img)))
+    }
+}
+
+mod vec2_serde {
+    use druid::Vec2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(v: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        (v.x, v.y).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        let (x, y) = <(f64, f64)>::deserialize(deserializer)?;
+        Ok(Vec2::new(x, y))
+    }
+}
+
 #[derive(Debug, Clone, Copy, Data)]
 pub(crate) struct PlaneIndex(usize);
 
-#[derive(Debug, Clone)]
+impl PlaneIndex {
+    pub(crate) fn new(index: usize) -> PlaneIndex {
+        PlaneIndex(index)
+    }
+}
+
+/// How a plane's pixels combine with the planes beneath it.
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+pub(crate) enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+}
+
+impl BlendMode {
+    /// `B(src, dst)` in normalized (0..1) space, before the Porter-Duff
+    /// "over" compositing that applies `src_a`/`dst_a`.
+    fn blend(self, src: f32, dst: f32) -> f32 {
+        match self {
+            BlendMode::Normal => src,
+            BlendMode::Multiply => src * dst,
+            BlendMode::Screen => 1.0 - (1.0 - src) * (1.0 - dst),
+            BlendMode::Overlay => {
+                if dst < 0.5 {
+                    2.0 * src * dst
+                } else {
+                    1.0 - 2.0 * (1.0 - src) * (1.0 - dst)
+                }
+            }
+        }
+    }
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Normal
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct PlaneData {
     inner: Arc<Plane>,
+    #[serde(with = "vec2_serde")]
     transform: Vec2,
+    blend: BlendMode,
+    opacity: f32,
+    visible: bool,
 }
 
 // FIXME: Move it to Canvas
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Planes {
     planes: Vec<PlaneData>,
+    /// Index into `planes` that new edits (e.g. `mov`) apply to. `None` only
+    /// when there are no planes.
+    active: Option<usize>,
 }
 
 impl Eq for Planes {}
 impl PartialEq for Planes {
     fn eq(&self, other: &Planes) -> bool {
-        if self.planes.len() != other.planes.len() {
+        if self.planes.len() != other.planes.len() || self.active != other.active {
             return false;
         }
-        self.planes.iter().zip(other.planes.iter()).all(|(a, b)| Arc::ptr_eq(&a.inner, &b.inner))
+        self.planes.iter().zip(other.planes.iter()).all(|(a, b)| {
+            Arc::ptr_eq(&a.inner, &b.inner)
+                && a.transform == b.transform
+                && a.blend == b.blend
+                && a.opacity == b.opacity
+                && a.visible == b.visible
+        })
     }
 }
 impl Data for Planes {
@@ -80,7 +176,7 @@ impl Data for Planes {
 
 impl Planes {
     pub(crate) fn new() -> Planes {
-        Planes { planes: Vec::new() }
+        Planes { planes: Vec::new(), active: None }
     }
 
     pub(crate) fn max_size(&self) -> Option<Size> {
@@ -97,34 +193,202 @@ impl Planes {
     }
 
     pub(crate) fn push(&mut self, plane: impl Into<Plane>) -> PlaneIndex {
-        self.planes.push(PlaneData { inner: Arc::new(plane.into()), transform: Vec2::ZERO });
-        PlaneIndex(self.planes.len() - 1)
+        self.planes.push(PlaneData {
+            inner: Arc::new(plane.into()),
+            transform: Vec2::ZERO,
+            blend: BlendMode::Normal,
+            opacity: 1.0,
+            visible: true,
+        });
+        let index = PlaneIndex(self.planes.len() - 1);
+        self.active = Some(index.0);
+        index
     }
 
     pub(crate) fn merged(&self) -> Option<Arc<DynamicImage>> {
         let size = self.max_size()?;
         let mut img = image::DynamicImage::new_rgba8(size.width as u32, size.height as u32);
 
-        for plane in &self.planes {
-            image_utils::merge_image(&mut img, &plane.inner.image(), plane.transform);
+        for plane in self.planes.iter().filter(|plane| plane.visible) {
+            composite_plane(&mut img, &plane.inner.image(), plane.transform, plane.blend, plane.opacity);
         }
 
         Some(Arc::new(img))
     }
 
+    /// The plane that `mov` and future edits apply to.
+    pub(crate) fn active(&self) -> Option<PlaneIndex> {
+        self.active.map(PlaneIndex)
+    }
+
+    pub(crate) fn set_active(&mut self, index: PlaneIndex) {
+        if index.0 < self.planes.len() {
+            self.active = Some(index.0);
+        }
+    }
+
     pub(crate) fn mov(&mut self, offset: Vec2) -> Option<Point> {
-        let plane = self.planes.last_mut()?;
+        let plane = self.planes.get_mut(self.active?)?;
         plane.transform += offset;
         Some(plane.transform.to_point())
     }
+
+    pub(crate) fn set_blend(&mut self, index: PlaneIndex, blend: BlendMode) {
+        if let Some(plane) = self.planes.get_mut(index.0) {
+            plane.blend = blend;
+        }
+    }
+
+    pub(crate) fn set_opacity(&mut self, index: PlaneIndex, opacity: f32) {
+        if let Some(plane) = self.planes.get_mut(index.0) {
+            plane.opacity = opacity.max(0.0).min(1.0);
+        }
+    }
+
+    pub(crate) fn set_visible(&mut self, index: PlaneIndex, visible: bool) {
+        if let Some(plane) = self.planes.get_mut(index.0) {
+            plane.visible = visible;
+        }
+    }
+
+    /// Removes the plane at `index`, shifting the active plane if needed.
+    pub(crate) fn remove(&mut self, index: PlaneIndex) {
+        if index.0 >= self.planes.len() {
+            return;
+        }
+        self.planes.remove(index.0);
+
+        self.active = self.active.and_then(|active| {
+            if self.planes.is_empty() {
+                None
+            } else if active > index.0 {
+                Some(active - 1)
+            } else if active == index.0 {
+                Some(active.min(self.planes.len() - 1))
+            } else {
+                Some(active)
+            }
+        });
+    }
+
+    /// Moves the plane at `from` to sit at `to`, shifting the planes between
+    /// them, and keeps `active` pointing at the same plane.
+    pub(crate) fn reorder(&mut self, from: PlaneIndex, to: PlaneIndex) {
+        if from.0 >= self.planes.len() || to.0 >= self.planes.len() || from.0 == to.0 {
+            return;
+        }
+
+        let active_plane_id = self.active.and_then(|i| self.planes.get(i)).map(|p| Arc::as_ptr(&p.inner));
+
+        let plane = self.planes.remove(from.0);
+        self.planes.insert(to.0, plane);
+
+        if let Some(active_plane_id) = active_plane_id {
+            self.active = self.planes.iter().position(|p| Arc::as_ptr(&p.inner) == active_plane_id);
+        }
+    }
+
+    /// Projects the layer stack into display rows for the layers panel,
+    /// topmost (last-painted) layer first.
+    pub(crate) fn layer_rows(&self) -> Vec<LayerRow> {
+        self.planes
+            .iter()
+            .enumerate()
+            .map(|(index, plane)| LayerRow {
+                index,
+                name: format!("Layer {}", index + 1),
+                visible: plane.visible,
+                active: self.active == Some(index),
+                thumbnail: plane.inner.image(),
+            })
+            .rev()
+            .collect()
+    }
+
+    /// Serializes the whole layer stack (pixels, transforms, metadata) into
+    /// the native `.paintr` document container.
+    pub(crate) fn to_document_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Restores a layer stack previously written by `to_document_bytes`.
+    pub(crate) fn from_document_bytes(bytes: &[u8]) -> Result<Planes, Box<dyn std::error::Error>> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Paints `plane` at `opacity`. Piet has no per-draw alpha outside of a
+/// blend mode, so anything less than fully opaque is rendered by scaling the
+/// plane's own alpha channel before handing it to the paint backend, mirroring
+/// the `opacity` term in `composite_plane`'s Porter-Duff math.
+fn paint_with_opacity(ctx: &mut impl RenderContext, plane: &Plane, opacity: f32) {
+    if opacity >= 1.0 {
+        plane.paint(ctx);
+        return;
+    }
+
+    let Plane::Image(img) = plane;
+    let mut faded = img.to_rgba8();
+    for pixel in faded.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+    Plane::Image(Arc::new(DynamicImage::ImageRgba8(faded))).paint(ctx);
+}
+
+/// Composites `src`, offset by `transform`, onto `dst` using `blend` and
+/// `opacity`, via Porter-Duff "over" in normalized float space.
+fn composite_plane(dst: &mut DynamicImage, src: &DynamicImage, transform: Vec2, blend: BlendMode, opacity: f32) {
+    let (dx, dy) = (transform.x as i64, transform.y as i64);
+
+    for (sx, sy, src_px) in src.pixels() {
+        let (x, y) = (sx as i64 + dx, sy as i64 + dy);
+        if x < 0 || y < 0 || x >= dst.width() as i64 || y >= dst.height() as i64 {
+            continue;
+        }
+        let (x, y) = (x as u32, y as u32);
+
+        let dst_px = dst.get_pixel(x, y);
+        let src_a = (src_px[3] as f32 / 255.0) * opacity;
+        let dst_a = dst_px[3] as f32 / 255.0;
+        let out_a = src_a + dst_a * (1.0 - src_a);
+
+        // Co = as(1-ab)Cs + as*ab*B(Cb,Cs) + (1-as)ab*Cb: the blended term
+        // only applies where both src and the backdrop actually cover the
+        // pixel, so a partially (or fully) transparent backdrop doesn't drag
+        // e.g. a Multiply-blended layer down towards black.
+        let out_rgb = if out_a == 0.0 {
+            [0.0; 3]
+        } else {
+            let mut out = [0.0; 3];
+            for i in 0..3 {
+                let s = src_px[i] as f32 / 255.0;
+                let d = dst_px[i] as f32 / 255.0;
+                let b = blend.blend(s, d);
+                out[i] = (src_a * (1.0 - dst_a) * s + src_a * dst_a * b + (1.0 - src_a) * dst_a * d) / out_a;
+            }
+            out
+        };
+
+        dst.put_pixel(
+            x,
+            y,
+            Rgba([
+                (out_rgb[0] * 255.0).round() as u8,
+                (out_rgb[1] * 255.0).round() as u8,
+                (out_rgb[2] * 255.0).round() as u8,
+                (out_a * 255.0).round() as u8,
+            ]),
+        );
+    }
 }
 
 impl Paintable for Planes {
     fn paint(&self, render_ctx: &mut impl RenderContext) {
-        for plane in &self.planes {
+        for plane in self.planes.iter().filter(|plane| plane.visible) {
             let _ = render_ctx.with_save(|ctx| {
                 ctx.transform(Affine::translate(plane.transform));
-                plane.inner.paint(ctx);
+                ctx.blend_mode(plane.blend.into());
+                paint_with_opacity(ctx, &plane.inner, plane.opacity);
                 Ok(())
             });
         }
@@ -133,3 +397,14 @@ impl Paintable for Planes {
         self.max_size()
     }
 }
+
+impl From<BlendMode> for druid::piet::BlendMode {
+    fn from(mode: BlendMode) -> Self {
+        match mode {
+            BlendMode::Normal => druid::piet::BlendMode::Normal,
+            BlendMode::Multiply => druid::piet::BlendMode::Multiply,
+            BlendMode::Screen => druid::piet::BlendMode::Screen,
+            BlendMode::Overlay => druid::piet::BlendMode::Overlay,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Persisted user preferences, loaded from and saved to
+//! `dirs::config_dir()/paintr/config.yaml`. Replaces the values that used to
+//! be hardcoded in `main()` (window size, background color, ...) with a
+//! config file the user can edit through a preferences dialog.
+
+use druid::{Color, Data, Selector};
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error>;
+
+/// Opens the preferences dialog with the delegate's current `Config`.
+pub(crate) const PREFERENCES_OPEN_ACTION: Selector = Selector::new("paintr.preferences-open");
+/// Sent once the preferences dialog is accepted, carrying the new `Config`
+/// to persist. The delegate uses it from then on (new-file size and fill);
+/// the window background, window size and autosave interval in effect are
+/// not changed until the next launch.
+pub(crate) const PREFERENCES_APPLY: Selector<Config> = Selector::new("paintr.preferences-apply");
+
+#[derive(Debug, Clone, Copy, PartialEq, Data, Serialize, Deserialize)]
+pub(crate) struct SerializableColor(pub u8, pub u8, pub u8);
+
+impl From<SerializableColor> for Color {
+    fn from(c: SerializableColor) -> Color {
+        Color::rgb8(c.0, c.1, c.2)
+    }
+}
+
+/// How many recently opened/saved paths to remember.
+const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Data, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub window_width: f64,
+    pub window_height: f64,
+    pub window_background: SerializableColor,
+    pub default_canvas_width: u32,
+    pub default_canvas_height: u32,
+    pub new_file_fill: SerializableColor,
+    pub autosave_interval_secs: u64,
+    pub recent_files: Arc<Vec<PathBuf>>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            window_width: 800.0,
+            window_height: 600.0,
+            window_background: SerializableColor(0, 0x77, 0x88),
+            default_canvas_width: 800,
+            default_canvas_height: 600,
+            new_file_fill: SerializableColor(0xff, 0xff, 0xff),
+            autosave_interval_secs: 30,
+            recent_files: Arc::new(Vec::new()),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file, falling back to defaults if it is missing or
+    /// malformed rather than failing app startup.
+    pub(crate) fn load() -> Config {
+        Self::try_load().unwrap_or_default()
+    }
+
+    fn try_load() -> Result<Config, Error> {
+        let contents = std::fs::read_to_string(config_path()?)?;
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    pub(crate) fn save(&self) -> Result<(), Error> {
+        let path = config_path()?;
+        std::fs::create_dir_all(path.parent().ok_or("Invalid config path")?)?;
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Records `path` as the most recently opened/saved document, moving it
+    /// to the front if already present and capping the list at
+    /// `MAX_RECENT_FILES`.
+    pub(crate) fn push_recent_file(&mut self, path: &Path) {
+        let mut recent = (*self.recent_files).clone();
+        recent.retain(|it| it != path);
+        recent.insert(0, path.to_path_buf());
+        recent.truncate(MAX_RECENT_FILES);
+        self.recent_files = Arc::new(recent);
+    }
+}
+
+fn config_path() -> Result<PathBuf, Error> {
+    Ok(dirs::config_dir().ok_or("Could not determine config directory")?.join("paintr/config.yaml"))
+}
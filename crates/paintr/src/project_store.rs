@@ -0,0 +1,106 @@
+//! Autosave / crash-recovery storage for in-progress documents.
+//!
+//! Every document the user is working on is periodically snapshotted into an
+//! embedded LMDB database living under `dirs::config_dir()/paintr/store/`, so
+//! that an unexpected exit doesn't lose the whole session. This is
+//! deliberately independent from `do_save_as_image`: it is not a file the
+//! user picked, just a crash-recovery log keyed by document id.
+
+use druid::Selector;
+use heed::types::{SerdeBincode, Str};
+use heed::{Database, Env, EnvOpenOptions};
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+
+pub type Error = Box<dyn std::error::Error>;
+
+/// Fired periodically (every `Config::autosave_interval_secs`) by a
+/// background thread so the delegate can flush a pending autosave without
+/// doing the PNG encode / LMDB write inline on every single edit command.
+pub(crate) const AUTOSAVE_TICK: Selector = Selector::new("paintr.autosave-tick");
+
+/// A point-in-time snapshot of a document, just enough to restore the
+/// session after a crash. The flattened image is kept rather than the full
+/// layer stack; `.paintr` documents (see `plane.rs`) are the format that
+/// preserves layers across an intentional save.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct DocumentSnapshot {
+    /// Path the document was last opened/saved from, if any.
+    pub path: Option<PathBuf>,
+    /// PNG-encoded flattened image.
+    pub image: Vec<u8>,
+}
+
+/// A document known to the store, as listed by `unsaved_documents` when the
+/// last session is restored at launch. The restore happens without asking
+/// the user first.
+#[derive(Debug, Clone)]
+pub(crate) struct RecoverableDocument {
+    pub doc_id: String,
+    pub path: Option<PathBuf>,
+}
+
+pub(crate) struct ProjectStore {
+    env: Env,
+    documents: Database<Str, SerdeBincode<DocumentSnapshot>>,
+}
+
+impl ProjectStore {
+    /// Opens (creating if necessary) the store under
+    /// `dirs::config_dir()/paintr/store/`.
+    pub(crate) fn open() -> Result<ProjectStore, Error> {
+        let dir = store_dir()?;
+        std::fs::create_dir_all(&dir)?;
+
+        let env = EnvOpenOptions::new().map_size(64 * 1024 * 1024).max_dbs(1).open(&dir)?;
+        let documents = env.create_database(Some("documents"))?;
+
+        Ok(ProjectStore { env, documents })
+    }
+
+    /// Snapshots `image` (already flattened, PNG-encoded) under `doc_id`,
+    /// overwriting any previous autosave for that document.
+    pub(crate) fn snapshot(
+        &self,
+        doc_id: &str,
+        path: Option<&Path>,
+        image: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut wtxn = self.env.write_txn()?;
+        let snapshot = DocumentSnapshot { path: path.map(Path::to_path_buf), image };
+        self.documents.put(&mut wtxn, doc_id, &snapshot)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Drops the autosave for `doc_id`, e.g. once the user has explicitly
+    /// saved the document to its final destination.
+    pub(crate) fn forget(&self, doc_id: &str) -> Result<(), Error> {
+        let mut wtxn = self.env.write_txn()?;
+        self.documents.delete(&mut wtxn, doc_id)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Lists documents that have an autosave waiting to be restored.
+    pub(crate) fn unsaved_documents(&self) -> Result<Vec<RecoverableDocument>, Error> {
+        let rtxn = self.env.read_txn()?;
+        let mut out = Vec::new();
+        for entry in self.documents.iter(&rtxn)? {
+            let (doc_id, snapshot) = entry?;
+            out.push(RecoverableDocument { doc_id: doc_id.to_owned(), path: snapshot.path });
+        }
+        Ok(out)
+    }
+
+    /// Restores the flattened PNG bytes previously snapshotted for `doc_id`.
+    pub(crate) fn restore(&self, doc_id: &str) -> Result<Option<Vec<u8>>, Error> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.documents.get(&rtxn, doc_id)?.map(|snapshot| snapshot.image))
+    }
+}
+
+fn store_dir() -> Result<PathBuf, Error> {
+    Ok(dirs::config_dir().ok_or("Could not determine config directory")?.join("paintr/store"))
+}
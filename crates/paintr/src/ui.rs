@@ -0,0 +1,16 @@
+use druid::widget::{Split, ZStack};
+use druid::Widget;
+use paintr_widgets::widgets;
+
+use crate::dialogs::modal_builder;
+use crate::layers_panel::layers_panel_builder;
+use crate::AppState;
+
+pub(crate) fn ui_builder() -> impl Widget<AppState> {
+    let content = Split::columns(canvas_area(), layers_panel_builder()).split_point(0.8).draggable(true);
+    ZStack::new(content).with_child(modal_builder())
+}
+
+fn canvas_area() -> impl Widget<AppState> {
+    widgets::canvas_builder()
+}